@@ -1,21 +1,174 @@
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use vm::archi::ProgramAddress;
+use vm::debugger::{Debugger, StopReason};
+use vm::host::DefaultHost;
 use vm::Instruction;
 
 mod vm;
 
 const CODE_SIZE: usize = 128_000;
 
-fn main() {
-    let src = std::fs::read_to_string("sample.maf").expect("unable to open file");
+struct Program {
+    code: Vec<Instruction>,
+    data: Vec<u8>,
+    labels: HashMap<String, ProgramAddress>,
+}
+
+fn load_program(path: &str) -> Program {
+    if path.ends_with(".mafb") {
+        let bytes = std::fs::read(path).expect("unable to open file");
+        let code = vm::bytecode::decode(&bytes).expect("could not decode bytecode");
+        Program { code, data: Vec::new(), labels: HashMap::new() }
+    } else {
+        let src = std::fs::read_to_string(path).expect("unable to open file");
+        let mut code = [Instruction::Halt; CODE_SIZE];
+        let assembled = vm::asm::code_from_str_with_labels(&src, &mut code).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        });
+        Program {
+            code: code[0..assembled.length as usize].to_vec(),
+            data: assembled.data,
+            labels: assembled.labels,
+        }
+    }
+}
+
+/// Assembles `path` without a label table, for callers that only need the
+/// instruction stream (e.g. `--encode`).
+fn assemble_plain(path: &str) -> Vec<Instruction> {
+    let src = std::fs::read_to_string(path).expect("unable to open file");
     let mut code = [Instruction::Halt; CODE_SIZE];
-    let code_len = vm::asm::code_from_str(&src, &mut code).expect("could not parse code") as usize;
-    for inst in &code[0..code_len] {
-        println!(">> {:?}", inst);
+    let length = vm::asm::code_from_str(&src, &mut code).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    });
+    code[0..length as usize].to_vec()
+}
+
+fn report(res: Result<(), vm::MachineError>) {
+    match res {
+        Ok(()) => println!("halted"),
+        Err(err) => println!("error: {}", err),
     }
+}
+
+fn run(program: &Program) {
     let mut machine = vm::Machine::new();
-    let res = machine.run(&code);
+    machine.load_data(&program.data);
+    let mut host = DefaultHost;
+    let res = machine.run(&program.code, &mut host);
     println!(
         "pc: {}, sp: {}, fp: {}, acc: {}",
         machine.pc, machine.sp, machine.fp, machine.acc
     );
-    println!("res: {:?}", res);
+    report(res);
+}
+
+fn disassemble(program: &Program) {
+    print!("{}", vm::disasm::code_to_str(&program.code));
+}
+
+fn encode(path: &str) {
+    let code = assemble_plain(path);
+    let mut out = Vec::new();
+    vm::bytecode::encode(&code, &mut out);
+    let out_path = format!("{path}b");
+    std::fs::write(&out_path, &out).expect("unable to write bytecode file");
+    println!("wrote {} bytes to {}", out.len(), out_path);
+}
+
+/// Drives `program` interactively: `step`/`s`, `continue`/`c`,
+/// `break <label-or-address>`/`b <label-or-address>`, `clear <address>`,
+/// `trace on`/`trace off`, `stack`, `calls`, `quit`/`q`. Reads commands from
+/// stdin until the program halts or the user quits.
+fn debug(program: &Program) {
+    let mut debugger = Debugger::new(&program.code, program.labels.clone());
+    debugger.machine.load_data(&program.data);
+    let mut host = DefaultHost;
+    let stdin = io::stdin();
+    print!("> ");
+    let _ = io::stdout().flush();
+    for line in stdin.lock().lines() {
+        let line = line.expect("unable to read stdin");
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("step") | Some("s") => {
+                let outcome: Result<vm::StepOutcome, vm::MachineError> = debugger.step(&mut host);
+                match outcome {
+                    Ok(vm::StepOutcome::Halted) => {
+                        println!("halted");
+                        break;
+                    }
+                    Ok(outcome) => println!("{:?}", outcome),
+                    Err(err) => println!("error: {}", err),
+                }
+            }
+            Some("continue") | Some("c") => match debugger.continue_execution(&mut host) {
+                Ok(StopReason::Halted) => {
+                    println!("halted");
+                    break;
+                }
+                Ok(StopReason::Breakpoint(addr)) => println!("breakpoint hit at {}", addr),
+                Err(err) => println!("error: {}", err),
+            },
+            Some("break") | Some("b") => match words.next() {
+                Some(operand) => match operand.parse::<ProgramAddress>() {
+                    Ok(addr) => {
+                        debugger.set_breakpoint(addr);
+                        println!("breakpoint set at {}", addr);
+                    }
+                    Err(_) if debugger.set_breakpoint_at_label(operand) => {
+                        println!("breakpoint set at {}", operand)
+                    }
+                    Err(_) => println!("unknown label: {}", operand),
+                },
+                None => println!("usage: break <label-or-address>"),
+            },
+            Some("clear") => match words.next().and_then(|operand| operand.parse::<ProgramAddress>().ok()) {
+                Some(addr) => {
+                    debugger.clear_breakpoint(addr);
+                    println!("breakpoint cleared at {}", addr);
+                }
+                None => println!("usage: clear <address>"),
+            },
+            Some("trace") => match words.next() {
+                Some("on") => debugger.trace = true,
+                Some("off") => debugger.trace = false,
+                _ => println!("usage: trace on|off"),
+            },
+            Some("stack") => println!("{:?}", debugger.operand_stack()),
+            Some("calls") => println!("{:?}", debugger.call_stack()),
+            Some("quit") | Some("q") => break,
+            Some(other) => println!("unknown command: {}", other),
+            None => {}
+        }
+        print!("> ");
+        let _ = io::stdout().flush();
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let mut path = "sample.maf";
+    let mut mode = "run";
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--disasm" => mode = "disasm",
+            "--debug" => mode = "debug",
+            "--encode" => mode = "encode",
+            other => path = other,
+        }
+        i += 1;
+    }
+
+    match mode {
+        "encode" => encode(path),
+        "disasm" => disassemble(&load_program(path)),
+        "debug" => debug(&load_program(path)),
+        _ => run(&load_program(path)),
+    }
 }
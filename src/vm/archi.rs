@@ -4,6 +4,37 @@ pub type Immediate = i16;
 
 pub const STACK_SIZE: usize = 16000;
 pub const CALLSTACK_SIZE: usize = 100;
+pub const MEM_SIZE: usize = 65536;
+pub const TRAP_COUNT: usize = 4;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TrapKind {
+    DivByZero,
+    StackOverflow,
+    CallStackOverflow,
+    MemoryFault,
+}
+
+impl TrapKind {
+    pub fn index(self) -> usize {
+        match self {
+            TrapKind::DivByZero => 0,
+            TrapKind::StackOverflow => 1,
+            TrapKind::CallStackOverflow => 2,
+            TrapKind::MemoryFault => 3,
+        }
+    }
+
+    pub fn from_index(index: u8) -> Option<TrapKind> {
+        match index {
+            0 => Some(TrapKind::DivByZero),
+            1 => Some(TrapKind::StackOverflow),
+            2 => Some(TrapKind::CallStackOverflow),
+            3 => Some(TrapKind::MemoryFault),
+            _ => None,
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Instruction {
@@ -34,4 +65,8 @@ pub enum Instruction {
     JumpIfNotZero(ProgramAddress),
     Call(ProgramAddress),
     Ret,
+    Store8,
+    Load8,
+    RegisterTrap(TrapKind, ProgramAddress),
+    Syscall(u8),
 }
@@ -1,7 +1,7 @@
 use core::str;
 use std::collections::{hash_map::Entry, HashMap};
 
-use super::archi::{Immediate, Instruction, ProgramAddress};
+use super::archi::{Immediate, Instruction, ProgramAddress, TrapKind};
 use std::ops::Range;
 
 #[derive(Debug)]
@@ -9,15 +9,40 @@ pub enum AssemblyErrorKind {
     UnknownInstruction(String),
     WrongArity { expected: usize, got: usize },
     InvalidNumber(String),
+    UnknownTrapKind(String),
+    MalformedDirective(String),
+    DuplicateLabel(String),
     CodeTooBig,
 }
 
+impl std::fmt::Display for AssemblyErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AssemblyErrorKind::UnknownInstruction(inst) => write!(f, "unknown instruction `{inst}`"),
+            AssemblyErrorKind::WrongArity { expected, got } => {
+                write!(f, "wrong number of operands: expected {expected}, got {got}")
+            }
+            AssemblyErrorKind::InvalidNumber(operand) => write!(f, "invalid number `{operand}`"),
+            AssemblyErrorKind::UnknownTrapKind(operand) => write!(f, "unknown trap kind `{operand}`"),
+            AssemblyErrorKind::MalformedDirective(directive) => write!(f, "malformed directive `{directive}`"),
+            AssemblyErrorKind::DuplicateLabel(name) => write!(f, "duplicate label `{name}`"),
+            AssemblyErrorKind::CodeTooBig => write!(f, "code too big"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct AssemblyError {
     pub kind: AssemblyErrorKind,
     pub line: usize,
 }
 
+impl std::fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.kind)
+    }
+}
+
 fn parse_immediate(operand: &str, linum: usize) -> Result<Immediate, AssemblyError> {
     operand.parse::<Immediate>().map_err(|_| AssemblyError {
         kind: AssemblyErrorKind::InvalidNumber(operand.to_string()),
@@ -25,6 +50,99 @@ fn parse_immediate(operand: &str, linum: usize) -> Result<Immediate, AssemblyErr
     })
 }
 
+fn parse_trap_kind(operand: &str, linum: usize) -> Result<TrapKind, AssemblyError> {
+    match operand {
+        "divzero" => Ok(TrapKind::DivByZero),
+        "stackoverflow" => Ok(TrapKind::StackOverflow),
+        "callstackoverflow" => Ok(TrapKind::CallStackOverflow),
+        "memfault" => Ok(TrapKind::MemoryFault),
+        _ => Err(AssemblyError {
+            kind: AssemblyErrorKind::UnknownTrapKind(operand.to_string()),
+            line: linum,
+        }),
+    }
+}
+
+/// Unescapes a `"..."` string literal (`\n`, `\t`, `\\`, `\"`) into raw bytes.
+fn parse_string_literal(literal: &str, line_num: usize) -> Result<Vec<u8>, AssemblyError> {
+    let inner = literal
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| AssemblyError {
+            kind: AssemblyErrorKind::MalformedDirective(literal.to_string()),
+            line: line_num,
+        })?;
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0u8; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next() {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('\\') => bytes.push(b'\\'),
+            Some('"') => bytes.push(b'"'),
+            Some(other) => {
+                bytes.push(b'\\');
+                let mut buf = [0u8; 4];
+                bytes.extend_from_slice(other.encode_utf8(&mut buf).as_bytes());
+            }
+            None => bytes.push(b'\\'),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Output of [`extract_directives`]: the remaining assembly text, the
+/// resolved `macro` constants, the flattened `.data` buffer, and a
+/// `label -> offset` table for each `.data` entry's start address.
+struct Directives {
+    stripped: String,
+    constants: HashMap<String, Immediate>,
+    data: Vec<u8>,
+    data_labels: HashMap<String, ProgramAddress>,
+}
+
+/// Preprocessing pass run before tokenizing: strips `macro NAME value end`
+/// constant definitions and `.data NAME "literal"` byte buffers out of the
+/// source.
+fn extract_directives(src: &str) -> Result<Directives, AssemblyError> {
+    let mut constants = HashMap::new();
+    let mut data = Vec::new();
+    let mut data_labels = HashMap::new();
+    let mut stripped = String::with_capacity(src.len());
+
+    for (line_num, line) in src.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(rest) = trimmed.strip_prefix("macro ") {
+            let mut tokens = rest.split_whitespace();
+            let name = tokens.next().unwrap_or("");
+            let value = tokens.next().unwrap_or("");
+            let immediate = value.parse::<Immediate>().map_err(|_| AssemblyError {
+                kind: AssemblyErrorKind::InvalidNumber(value.to_string()),
+                line: line_num,
+            })?;
+            constants.insert(name.to_string(), immediate);
+        } else if let Some(rest) = trimmed.strip_prefix(".data ") {
+            let (name, literal) = rest.trim_start().split_once(' ').ok_or_else(|| AssemblyError {
+                kind: AssemblyErrorKind::MalformedDirective(rest.to_string()),
+                line: line_num,
+            })?;
+            let bytes = parse_string_literal(literal.trim(), line_num)?;
+            data_labels.insert(name.to_string(), data.len() as ProgramAddress);
+            data.extend_from_slice(&bytes);
+            data.push(0);
+        } else {
+            stripped.push_str(line);
+            stripped.push('\n');
+        }
+    }
+    Ok(Directives { stripped, constants, data, data_labels })
+}
+
 struct Fix {
     address: Option<ProgramAddress>,
     to_fix: Vec<ProgramAddress>,
@@ -78,17 +196,17 @@ impl Tokenizer {
         str::from_utf8(&source[self.next_token(source)]).expect("valid utf8")
     }
 
-    fn parse_address<'a>(
+    fn parse_address(
         &self,
-        operand: &'a str,
+        operand: &str,
         inst_count: ProgramAddress,
-        fixes_by_label: &mut HashMap<&'a str, Fix>,
+        fixes_by_label: &mut HashMap<String, Fix>,
     ) -> Result<ProgramAddress, AssemblyError> {
         if operand.starts_with('@') {
             let label = &operand[1..operand.len()];
             match fixes_by_label.get_mut(label) {
                 None => {
-                    fixes_by_label.insert(label, Fix::new_with_fix(None, inst_count));
+                    fixes_by_label.insert(label.to_string(), Fix::new_with_fix(None, inst_count));
                     return Ok(0);
                 }
                 Some(fix) => {
@@ -107,15 +225,40 @@ impl Tokenizer {
     }
 }
 
+/// Output of a full assembly pass: the instruction count, the resolved
+/// `label -> address` table, and the flattened `.data` byte buffer (to be
+/// loaded into the machine's memory before running the code).
+pub struct Assembled {
+    pub length: ProgramAddress,
+    pub labels: HashMap<String, ProgramAddress>,
+    pub data: Vec<u8>,
+}
+
 pub fn code_from_str(src: &str, dst: &mut [Instruction]) -> Result<ProgramAddress, AssemblyError> {
+    Ok(assemble(src, dst)?.length)
+}
+
+/// Like [`code_from_str`], but also returns the resolved label table and
+/// `.data` buffer so tools like the debugger can set breakpoints by label
+/// name and callers can populate memory before running the code.
+pub fn code_from_str_with_labels(src: &str, dst: &mut [Instruction]) -> Result<Assembled, AssemblyError> {
+    assemble(src, dst)
+}
+
+fn assemble(src: &str, dst: &mut [Instruction]) -> Result<Assembled, AssemblyError> {
+    let Directives { stripped, constants, data, data_labels } = extract_directives(src)?;
+
     let mut tokenizer = Tokenizer {
         char_index: 0,
         last_token: 0,
         line_num: 0,
     };
     let mut inst_count: ProgramAddress = 0;
-    let mut fixes_by_label: HashMap<&str, Fix> = HashMap::new();
-    let source = src.as_bytes();
+    let mut fixes_by_label: HashMap<String, Fix> = HashMap::new();
+    for (name, addr) in data_labels {
+        fixes_by_label.insert(name, Fix::new(Some(addr)));
+    }
+    let source = stripped.as_bytes();
 
     while tokenizer.char_index < source.len() {
         match source[tokenizer.char_index] {
@@ -126,10 +269,21 @@ pub fn code_from_str(src: &str, dst: &mut [Instruction]) -> Result<ProgramAddres
                     b"" => { continue; },
                     b"halt" => Instruction::Halt,
                     b"noop" => Instruction::Noop,
-                    b"load" => Instruction::LoadImmediate(parse_immediate(
-                        tokenizer.next_token_slice(source),
-                        tokenizer.line_num,
-                    )?),
+                    b"load" => {
+                        let operand = tokenizer.next_token_slice(source);
+                        let immediate = if operand.starts_with('@') {
+                            tokenizer.parse_address(operand, inst_count, &mut fixes_by_label)? as Immediate
+                        } else {
+                            match operand.parse::<Immediate>() {
+                                Ok(n) => n,
+                                Err(_) => match constants.get(operand) {
+                                    Some(&n) => n,
+                                    None => parse_immediate(operand, tokenizer.line_num)?,
+                                },
+                            }
+                        };
+                        Instruction::LoadImmediate(immediate)
+                    }
                     b"push" => Instruction::Push,
                     b"pop" => Instruction::Pop,
                     b"dup" => Instruction::Dup,
@@ -182,16 +336,40 @@ pub fn code_from_str(src: &str, dst: &mut [Instruction]) -> Result<ProgramAddres
                         )?)
                     }
                     b"ret" => Instruction::Ret,
+                    b"st8" => Instruction::Store8,
+                    b"ld8" => Instruction::Load8,
+                    b"syscall" => {
+                        let num = tokenizer.next_token_slice(source);
+                        Instruction::Syscall(num.parse::<u8>().map_err(|_| AssemblyError {
+                            kind: AssemblyErrorKind::InvalidNumber(num.to_string()),
+                            line: tokenizer.line_num,
+                        })?)
+                    }
+                    b"trap" => {
+                        let kind = parse_trap_kind(
+                            tokenizer.next_token_slice(source),
+                            tokenizer.line_num,
+                        )?;
+                        let addr = tokenizer.next_token_slice(source);
+                        Instruction::RegisterTrap(
+                            kind,
+                            tokenizer.parse_address(addr, inst_count, &mut fixes_by_label)?,
+                        )
+                    }
                     label if label.len() > 0 && label[label.len() - 1] == b':' => {
-                        let entry = fixes_by_label
-                            .entry(str::from_utf8(&label[0..label.len() - 1]).expect("valid utf8"));
-                        match entry {
+                        let name = str::from_utf8(&label[0..label.len() - 1]).expect("valid utf8").to_string();
+                        match fixes_by_label.entry(name.clone()) {
+                            Entry::Occupied(ent) if ent.get().address.is_some() => {
+                                Err(AssemblyError {
+                                    kind: AssemblyErrorKind::DuplicateLabel(name),
+                                    line: tokenizer.line_num,
+                                })?
+                            }
                             Entry::Occupied(ent) => {
                                 ent.into_mut().address = Some(inst_count);
                             }
                             Entry::Vacant(ent) => {
-                                let key = ent.into_key();
-                                fixes_by_label.insert(key, Fix::new(Some(inst_count)));
+                                ent.insert(Fix::new(Some(inst_count)));
                             }
                         }
                         continue;
@@ -211,10 +389,12 @@ pub fn code_from_str(src: &str, dst: &mut [Instruction]) -> Result<ProgramAddres
             }
         }
     }
-    for (_, fix) in fixes_by_label {
+    let mut labels = HashMap::new();
+    for (name, fix) in fixes_by_label {
         match fix.address {
             None => todo!("handle error missing label definition"),
             Some(address) => {
+                labels.insert(name, address);
                 for to_fix in fix.to_fix {
                     let addr = to_fix as usize;
                     match dst[addr] {
@@ -224,11 +404,51 @@ pub fn code_from_str(src: &str, dst: &mut [Instruction]) -> Result<ProgramAddres
                             dst[addr] = Instruction::JumpIfNotZero(address)
                         }
                         Instruction::Call(_) => dst[addr] = Instruction::Call(address),
+                        Instruction::RegisterTrap(kind, _) => {
+                            dst[addr] = Instruction::RegisterTrap(kind, address)
+                        }
+                        Instruction::LoadImmediate(_) => {
+                            dst[addr] = Instruction::LoadImmediate(address as Immediate)
+                        }
                         _ => todo!("handle error instruction address unsupported"),
                     }
                 }
             }
         }
     }
-    Ok(inst_count)
+    Ok(Assembled {
+        length: inst_count,
+        labels,
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_label_loads_as_an_address() {
+        let mut code = [Instruction::Halt; 16];
+        let assembled = code_from_str_with_labels(".data msg \"Hi\\n\"\nload @msg\nhalt\n", &mut code)
+            .expect("a .data label should be usable as a load operand");
+        assert_eq!(code[0], Instruction::LoadImmediate(0));
+        assert_eq!(assembled.data, b"Hi\n\0");
+    }
+
+    #[test]
+    fn label_past_last_instruction_assembles() {
+        let mut code = [Instruction::Halt; 16];
+        let assembled = code_from_str_with_labels("jz @end\nload 1\nend:\n", &mut code)
+            .expect("a label at the virtual end-of-code address should resolve");
+        assert_eq!(code[0], Instruction::JumpIfZero(assembled.length));
+    }
+
+    #[test]
+    fn macro_constant_substitutes_into_load() {
+        let mut code = [Instruction::Halt; 16];
+        code_from_str("macro WIDTH 640\nload WIDTH\n", &mut code)
+            .expect("a macro constant should be usable as a load operand");
+        assert_eq!(code[0], Instruction::LoadImmediate(640));
+    }
 }
@@ -0,0 +1,173 @@
+use super::archi::{Immediate, Instruction, ProgramAddress, TrapKind};
+
+#[derive(Debug, PartialEq)]
+pub enum DecodeError {
+    UnknownOpcode(u8),
+    UnexpectedEnd,
+}
+
+fn opcode(instruction: &Instruction) -> u8 {
+    match instruction {
+        Instruction::Halt => 0,
+        Instruction::Noop => 1,
+        Instruction::LoadImmediate(_) => 2,
+        Instruction::Push => 3,
+        Instruction::Pop => 4,
+        Instruction::Dup => 5,
+        Instruction::Swap => 6,
+        Instruction::LoadTop => 7,
+        Instruction::Over => 8,
+        Instruction::Inc => 9,
+        Instruction::Dec => 10,
+        Instruction::Add => 11,
+        Instruction::Sub => 12,
+        Instruction::Mul => 13,
+        Instruction::Div => 14,
+        Instruction::Eq => 15,
+        Instruction::Neq => 16,
+        Instruction::Lt => 17,
+        Instruction::Lte => 18,
+        Instruction::Gt => 19,
+        Instruction::Gte => 20,
+        Instruction::Inv => 21,
+        Instruction::Jmp(_) => 22,
+        Instruction::JumpIfZero(_) => 23,
+        Instruction::JumpIfNotZero(_) => 24,
+        Instruction::Call(_) => 25,
+        Instruction::Ret => 26,
+        Instruction::Store8 => 27,
+        Instruction::Load8 => 28,
+        Instruction::RegisterTrap(_, _) => 29,
+        Instruction::Syscall(_) => 30,
+    }
+}
+
+/// Appends the encoded form of `code` to `out`: one opcode byte followed by
+/// the little-endian bytes of its operand, if any.
+pub fn encode(code: &[Instruction], out: &mut Vec<u8>) {
+    for instruction in code {
+        out.push(opcode(instruction));
+        match instruction {
+            Instruction::LoadImmediate(n) => out.extend_from_slice(&n.to_le_bytes()),
+            Instruction::Jmp(addr)
+            | Instruction::JumpIfZero(addr)
+            | Instruction::JumpIfNotZero(addr)
+            | Instruction::Call(addr) => out.extend_from_slice(&addr.to_le_bytes()),
+            Instruction::RegisterTrap(kind, addr) => {
+                out.push(kind.index() as u8);
+                out.extend_from_slice(&addr.to_le_bytes());
+            }
+            Instruction::Syscall(num) => out.push(*num),
+            _ => {}
+        }
+    }
+}
+
+fn read_immediate(bytes: &[u8], index: &mut usize) -> Result<Immediate, DecodeError> {
+    let slice = bytes
+        .get(*index..*index + 2)
+        .ok_or(DecodeError::UnexpectedEnd)?;
+    *index += 2;
+    Ok(Immediate::from_le_bytes([slice[0], slice[1]]))
+}
+
+fn read_address(bytes: &[u8], index: &mut usize) -> Result<ProgramAddress, DecodeError> {
+    let slice = bytes
+        .get(*index..*index + 2)
+        .ok_or(DecodeError::UnexpectedEnd)?;
+    *index += 2;
+    Ok(ProgramAddress::from_le_bytes([slice[0], slice[1]]))
+}
+
+/// Decodes a flat buffer produced by [`encode`] back into a sequence of
+/// [`Instruction`]s.
+pub fn decode(bytes: &[u8]) -> Result<Vec<Instruction>, DecodeError> {
+    let mut code = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let op = bytes[index];
+        index += 1;
+        let instruction = match op {
+            0 => Instruction::Halt,
+            1 => Instruction::Noop,
+            2 => Instruction::LoadImmediate(read_immediate(bytes, &mut index)?),
+            3 => Instruction::Push,
+            4 => Instruction::Pop,
+            5 => Instruction::Dup,
+            6 => Instruction::Swap,
+            7 => Instruction::LoadTop,
+            8 => Instruction::Over,
+            9 => Instruction::Inc,
+            10 => Instruction::Dec,
+            11 => Instruction::Add,
+            12 => Instruction::Sub,
+            13 => Instruction::Mul,
+            14 => Instruction::Div,
+            15 => Instruction::Eq,
+            16 => Instruction::Neq,
+            17 => Instruction::Lt,
+            18 => Instruction::Lte,
+            19 => Instruction::Gt,
+            20 => Instruction::Gte,
+            21 => Instruction::Inv,
+            22 => Instruction::Jmp(read_address(bytes, &mut index)?),
+            23 => Instruction::JumpIfZero(read_address(bytes, &mut index)?),
+            24 => Instruction::JumpIfNotZero(read_address(bytes, &mut index)?),
+            25 => Instruction::Call(read_address(bytes, &mut index)?),
+            26 => Instruction::Ret,
+            27 => Instruction::Store8,
+            28 => Instruction::Load8,
+            29 => {
+                let kind_byte = *bytes.get(index).ok_or(DecodeError::UnexpectedEnd)?;
+                index += 1;
+                let kind = TrapKind::from_index(kind_byte).ok_or(DecodeError::UnknownOpcode(kind_byte))?;
+                Instruction::RegisterTrap(kind, read_address(bytes, &mut index)?)
+            }
+            30 => {
+                let num = *bytes.get(index).ok_or(DecodeError::UnexpectedEnd)?;
+                index += 1;
+                Instruction::Syscall(num)
+            }
+            unknown => return Err(DecodeError::UnknownOpcode(unknown)),
+        };
+        code.push(instruction);
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::archi::TrapKind;
+
+    #[test]
+    fn encode_decode_round_trips_every_operand_shape() {
+        let code = vec![
+            Instruction::Halt,
+            Instruction::LoadImmediate(-7),
+            Instruction::Jmp(12),
+            Instruction::JumpIfZero(3),
+            Instruction::JumpIfNotZero(4),
+            Instruction::Call(5),
+            Instruction::RegisterTrap(TrapKind::MemoryFault, 9),
+            Instruction::Syscall(2),
+            Instruction::Add,
+        ];
+        let mut encoded = Vec::new();
+        encode(&code, &mut encoded);
+        assert_eq!(decode(&encoded).unwrap(), code);
+    }
+
+    #[test]
+    fn decode_errors_on_truncated_operand() {
+        let mut encoded = Vec::new();
+        encode(&[Instruction::Jmp(12)], &mut encoded);
+        encoded.truncate(encoded.len() - 1);
+        assert_eq!(decode(&encoded), Err(DecodeError::UnexpectedEnd));
+    }
+
+    #[test]
+    fn decode_errors_on_unknown_opcode() {
+        assert_eq!(decode(&[255]), Err(DecodeError::UnknownOpcode(255)));
+    }
+}
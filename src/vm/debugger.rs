@@ -0,0 +1,141 @@
+use std::collections::{HashMap, HashSet};
+
+use super::archi::{Instruction, ProgramAddress, Value};
+use super::host::Host;
+use super::machine::{Machine, MachineError, StepOutcome};
+
+/// Why [`Debugger::continue_execution`] stopped.
+#[derive(Debug, PartialEq)]
+pub enum StopReason {
+    Halted,
+    Breakpoint(ProgramAddress),
+}
+
+/// Wraps a [`Machine`] with single-stepping, breakpoints (by address or by
+/// assembler label) and an instruction trace, driven externally via
+/// [`Machine::step`] instead of [`Machine::run`]'s all-or-nothing loop.
+pub struct Debugger<'a> {
+    pub machine: Machine,
+    code: &'a [Instruction],
+    labels: HashMap<String, ProgramAddress>,
+    breakpoints: HashSet<ProgramAddress>,
+    pub trace: bool,
+}
+
+impl<'a> Debugger<'a> {
+    pub fn new(code: &'a [Instruction], labels: HashMap<String, ProgramAddress>) -> Debugger<'a> {
+        Debugger {
+            machine: Machine::new(),
+            code,
+            labels,
+            breakpoints: HashSet::new(),
+            trace: false,
+        }
+    }
+
+    pub fn set_breakpoint(&mut self, addr: ProgramAddress) {
+        self.breakpoints.insert(addr);
+    }
+
+    /// Resolves `label` through the assembler's label table and sets a
+    /// breakpoint there. Returns `false` if the label is unknown.
+    pub fn set_breakpoint_at_label(&mut self, label: &str) -> bool {
+        match self.labels.get(label) {
+            Some(&addr) => {
+                self.breakpoints.insert(addr);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear_breakpoint(&mut self, addr: ProgramAddress) {
+        self.breakpoints.remove(&addr);
+    }
+
+    /// Executes a single instruction, printing a trace line first when
+    /// [`Debugger::trace`] is enabled.
+    pub fn step(&mut self, host: &mut dyn Host) -> Result<StepOutcome, MachineError> {
+        if self.trace {
+            if let Some(instruction) = self.code.get(self.machine.pc as usize) {
+                println!(">> {:?}", instruction);
+            }
+        }
+        let outcome = self.machine.step(self.code, host)?;
+        if self.trace {
+            println!(
+                "pc: {}, sp: {}, fp: {}, acc: {}",
+                self.machine.pc, self.machine.sp, self.machine.fp, self.machine.acc
+            );
+        }
+        Ok(outcome)
+    }
+
+    /// Steps until a breakpoint is hit or the program halts.
+    pub fn continue_execution(&mut self, host: &mut dyn Host) -> Result<StopReason, MachineError> {
+        loop {
+            match self.step(host)? {
+                StepOutcome::Halted => return Ok(StopReason::Halted),
+                StepOutcome::Stepped => {
+                    if self.breakpoints.contains(&self.machine.pc) {
+                        return Ok(StopReason::Breakpoint(self.machine.pc));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn operand_stack(&self) -> &[Value] {
+        &self.machine.stack[0..self.machine.sp]
+    }
+
+    pub fn call_stack(&self) -> &[ProgramAddress] {
+        &self.machine.call_stack[0..self.machine.fp]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopHost;
+    impl Host for NoopHost {
+        fn syscall(&mut self, _num: u8, _m: &mut Machine) -> Result<(), MachineError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn steps_and_breaks_at_a_label() {
+        let code = [
+            Instruction::LoadImmediate(1),
+            Instruction::LoadImmediate(2),
+            Instruction::Halt,
+        ];
+        let mut labels = HashMap::new();
+        labels.insert("mid".to_string(), 1);
+        let mut debugger = Debugger::new(&code, labels);
+        assert!(debugger.set_breakpoint_at_label("mid"));
+        assert!(!debugger.set_breakpoint_at_label("nope"));
+
+        let mut host = NoopHost;
+        let reason = debugger.continue_execution(&mut host).expect("should not fault");
+        assert_eq!(reason, StopReason::Breakpoint(1));
+        assert_eq!(debugger.machine.acc, 1);
+
+        let reason = debugger.continue_execution(&mut host).expect("should not fault");
+        assert_eq!(reason, StopReason::Halted);
+        assert_eq!(debugger.machine.acc, 2);
+    }
+
+    #[test]
+    fn single_step_advances_pc() {
+        let code = [Instruction::LoadImmediate(7), Instruction::Halt];
+        let mut debugger = Debugger::new(&code, HashMap::new());
+        let mut host = NoopHost;
+        let outcome = debugger.step(&mut host).expect("should not fault");
+        assert_eq!(outcome, StepOutcome::Stepped);
+        assert_eq!(debugger.machine.acc, 7);
+        assert_eq!(debugger.machine.pc, 1);
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use super::archi::{Instruction, ProgramAddress, TrapKind};
+
+fn trap_kind_name(kind: TrapKind) -> &'static str {
+    match kind {
+        TrapKind::DivByZero => "divzero",
+        TrapKind::StackOverflow => "stackoverflow",
+        TrapKind::CallStackOverflow => "callstackoverflow",
+        TrapKind::MemoryFault => "memfault",
+    }
+}
+
+fn branch_target(instruction: &Instruction) -> Option<ProgramAddress> {
+    match instruction {
+        Instruction::Jmp(addr)
+        | Instruction::JumpIfZero(addr)
+        | Instruction::JumpIfNotZero(addr)
+        | Instruction::Call(addr)
+        | Instruction::RegisterTrap(_, addr) => Some(*addr),
+        _ => None,
+    }
+}
+
+fn collect_labels(code: &[Instruction]) -> HashMap<ProgramAddress, String> {
+    let mut targets: Vec<ProgramAddress> = code.iter().filter_map(branch_target).collect();
+    targets.sort_unstable();
+    targets.dedup();
+    targets
+        .into_iter()
+        .enumerate()
+        .map(|(i, addr)| (addr, format!("L{}", i)))
+        .collect()
+}
+
+/// Renders a single instruction back to its mnemonic form, resolving any
+/// branch target through `labels` into a symbolic `@name`.
+pub fn format_instruction(instruction: &Instruction, labels: &HashMap<ProgramAddress, String>) -> String {
+    match instruction {
+        Instruction::Halt => "halt".to_string(),
+        Instruction::Noop => "noop".to_string(),
+        Instruction::LoadImmediate(n) => format!("load {}", n),
+        Instruction::Push => "push".to_string(),
+        Instruction::Pop => "pop".to_string(),
+        Instruction::Dup => "dup".to_string(),
+        Instruction::Swap => "swap".to_string(),
+        Instruction::LoadTop => "ldt".to_string(),
+        Instruction::Over => "over".to_string(),
+        Instruction::Inc => "inc".to_string(),
+        Instruction::Dec => "dec".to_string(),
+        Instruction::Add => "add".to_string(),
+        Instruction::Sub => "sub".to_string(),
+        Instruction::Mul => "mul".to_string(),
+        Instruction::Div => "div".to_string(),
+        Instruction::Eq => "eq".to_string(),
+        Instruction::Neq => "neq".to_string(),
+        Instruction::Lt => "lt".to_string(),
+        Instruction::Lte => "lte".to_string(),
+        Instruction::Gt => "gt".to_string(),
+        Instruction::Gte => "gte".to_string(),
+        Instruction::Inv => "inv".to_string(),
+        Instruction::Jmp(addr) => format!("jmp @{}", labels[addr]),
+        Instruction::JumpIfZero(addr) => format!("jz @{}", labels[addr]),
+        Instruction::JumpIfNotZero(addr) => format!("jnz @{}", labels[addr]),
+        Instruction::Call(addr) => format!("call @{}", labels[addr]),
+        Instruction::Ret => "ret".to_string(),
+        Instruction::Store8 => "st8".to_string(),
+        Instruction::Load8 => "ld8".to_string(),
+        Instruction::RegisterTrap(kind, addr) => {
+            format!("trap {} @{}", trap_kind_name(*kind), labels[addr])
+        }
+        Instruction::Syscall(num) => format!("syscall {}", num),
+    }
+}
+
+/// Renders `code` back to assembly text accepted by [`super::asm::code_from_str`].
+///
+/// Branch targets are reconstructed into `@L0`, `@L1`, ... labels, each
+/// preceded by its own `Ln:` definition line.
+pub fn code_to_str(code: &[Instruction]) -> String {
+    let labels = collect_labels(code);
+    let mut out = String::new();
+    for (addr, instruction) in code.iter().enumerate() {
+        if let Some(label) = labels.get(&(addr as ProgramAddress)) {
+            let _ = writeln!(out, "{}:", label);
+        }
+        let _ = writeln!(out, "{}", format_instruction(instruction, &labels));
+    }
+    // A branch can legally target one past the last instruction (e.g. to
+    // fall through to an implicit halt); that address has no instruction
+    // slot to hang a label off of, so emit its definition on its own line.
+    if let Some(label) = labels.get(&(code.len() as ProgramAddress)) {
+        let _ = writeln!(out, "{}:", label);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_label_is_emitted_for_branch_past_last_instruction() {
+        let code = [Instruction::JumpIfZero(2), Instruction::LoadImmediate(1)];
+        let text = code_to_str(&code);
+        assert!(text.contains("L0:"), "missing definition for the end-of-code label:\n{}", text);
+
+        let mut reassembled = [Instruction::Halt; 16];
+        crate::vm::asm::code_from_str(&text, &mut reassembled)
+            .expect("round-tripped text should reassemble without panicking");
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::Write;
+
+use super::machine::{Machine, MachineError, MachineErrorKind};
+
+/// Lets a guest program talk to the outside world via `syscall`.
+///
+/// `Machine::run` calls [`Host::syscall`] whenever it executes an
+/// `Instruction::Syscall`, passing the raw syscall number and the machine
+/// so the handler can read the accumulator/stack/memory and react.
+pub trait Host {
+    fn syscall(&mut self, num: u8, m: &mut Machine) -> Result<(), MachineError>;
+}
+
+/// The default host ABI:
+/// - `syscall 0`: exit the process with `acc` as the status code.
+/// - `syscall 1`: write the low byte of `acc` to stdout.
+/// - `syscall 2`: pop a length then an address off the stack and write
+///   that byte range of memory to stdout.
+pub struct DefaultHost;
+
+impl Host for DefaultHost {
+    fn syscall(&mut self, num: u8, m: &mut Machine) -> Result<(), MachineError> {
+        match num {
+            0 => std::process::exit(m.acc),
+            1 => {
+                let _ = std::io::stdout().write_all(&[m.acc as u8]);
+                Ok(())
+            }
+            2 => {
+                let len = m.pop()?;
+                let addr = m.pop()?;
+                let bytes = m.read_bytes(addr, len)?;
+                let _ = std::io::stdout().write_all(bytes);
+                Ok(())
+            }
+            unknown => Err(MachineError::new(MachineErrorKind::UnknownSyscall(unknown), m.pc)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mirrors `DefaultHost`'s syscall 1/2 handling but records output
+    /// instead of writing to stdout, so tests can assert on it in-process.
+    struct RecordingHost {
+        output: Vec<u8>,
+    }
+
+    impl Host for RecordingHost {
+        fn syscall(&mut self, num: u8, m: &mut Machine) -> Result<(), MachineError> {
+            match num {
+                1 => {
+                    self.output.push(m.acc as u8);
+                    Ok(())
+                }
+                2 => {
+                    let len = m.pop()?;
+                    let addr = m.pop()?;
+                    let bytes = m.read_bytes(addr, len)?;
+                    self.output.extend_from_slice(bytes);
+                    Ok(())
+                }
+                unknown => Err(MachineError::new(MachineErrorKind::UnknownSyscall(unknown), m.pc)),
+            }
+        }
+    }
+
+    #[test]
+    fn syscall_1_writes_low_byte_of_acc() {
+        let mut machine = Machine::new();
+        machine.acc = b'A' as i32;
+        let mut host = RecordingHost { output: Vec::new() };
+        host.syscall(1, &mut machine).expect("syscall 1 should not fault");
+        assert_eq!(host.output, vec![b'A']);
+    }
+
+    #[test]
+    fn syscall_2_pops_length_then_address() {
+        let mut machine = Machine::new();
+        machine.load_data(b"hi");
+        machine.push(0).expect("stack has room"); // address, popped second
+        machine.push(2).expect("stack has room"); // length, popped first
+        let mut host = RecordingHost { output: Vec::new() };
+        host.syscall(2, &mut machine).expect("syscall 2 should not fault");
+        assert_eq!(host.output, b"hi");
+    }
+
+    #[test]
+    fn unmapped_syscall_reports_unknown_syscall() {
+        let mut machine = Machine::new();
+        let mut host = DefaultHost;
+        let err = host.syscall(99, &mut machine).expect_err("99 is not a mapped syscall");
+        assert_eq!(err.to_string(), "unknown syscall 99 (pc 0)");
+    }
+}
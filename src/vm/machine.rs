@@ -1,4 +1,5 @@
-use super::archi::{ Instruction, ProgramAddress, Value, CALLSTACK_SIZE, STACK_SIZE };
+use super::archi::{ Instruction, ProgramAddress, TrapKind, Value, CALLSTACK_SIZE, MEM_SIZE, STACK_SIZE, TRAP_COUNT };
+use super::host::Host;
 
 pub struct Machine {
     pub pc: ProgramAddress,
@@ -7,6 +8,8 @@ pub struct Machine {
     pub acc: Value,
     pub stack: [Value; STACK_SIZE],
     pub call_stack: [ProgramAddress; CALLSTACK_SIZE],
+    pub mem: [u8; MEM_SIZE],
+    pub trap_handlers: [Option<ProgramAddress>; TRAP_COUNT],
 }
 
 #[derive(Debug)]
@@ -15,7 +18,38 @@ pub enum MachineErrorKind {
     CallStackUnderflow,
     StackOverflow,
     StackUnderflow,
-} 
+    MemoryOutOfBounds { addr: Value },
+    DivisionByZero,
+    UnknownSyscall(u8),
+}
+
+impl std::fmt::Display for MachineErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MachineErrorKind::CallStackOverflow => write!(f, "call stack overflow"),
+            MachineErrorKind::CallStackUnderflow => write!(f, "call stack underflow"),
+            MachineErrorKind::StackOverflow => write!(f, "stack overflow"),
+            MachineErrorKind::StackUnderflow => write!(f, "stack underflow"),
+            MachineErrorKind::MemoryOutOfBounds { addr } => write!(f, "memory access out of bounds at {addr}"),
+            MachineErrorKind::DivisionByZero => write!(f, "division by zero"),
+            MachineErrorKind::UnknownSyscall(num) => write!(f, "unknown syscall {num}"),
+        }
+    }
+}
+
+impl MachineErrorKind {
+    fn trap_kind(&self) -> Option<TrapKind> {
+        match self {
+            MachineErrorKind::DivisionByZero => Some(TrapKind::DivByZero),
+            MachineErrorKind::StackOverflow => Some(TrapKind::StackOverflow),
+            MachineErrorKind::CallStackOverflow => Some(TrapKind::CallStackOverflow),
+            MachineErrorKind::MemoryOutOfBounds { .. } => Some(TrapKind::MemoryFault),
+            MachineErrorKind::CallStackUnderflow
+            | MachineErrorKind::StackUnderflow
+            | MachineErrorKind::UnknownSyscall(_) => None,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct MachineError {
@@ -23,6 +57,18 @@ pub struct MachineError {
     pc: ProgramAddress,
 }
 
+impl MachineError {
+    pub(crate) fn new(kind: MachineErrorKind, pc: ProgramAddress) -> MachineError {
+        MachineError { kind, pc }
+    }
+}
+
+impl std::fmt::Display for MachineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (pc {})", self.kind, self.pc)
+    }
+}
+
 impl Machine {
     pub fn new() -> Machine {
         Machine {
@@ -32,7 +78,37 @@ impl Machine {
             acc: 0,
             stack: [0; STACK_SIZE],
             call_stack: [0; CALLSTACK_SIZE],
+            mem: [0; MEM_SIZE],
+            trap_handlers: [None; TRAP_COUNT],
+        }
+    }
+
+    /// Copies an assembled `.data` buffer (see [`super::asm::Assembled`])
+    /// into the start of memory, truncating if it doesn't fit.
+    pub fn load_data(&mut self, data: &[u8]) {
+        let len = data.len().min(self.mem.len());
+        self.mem[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn check_addr(&self, addr: Value) -> Result<usize, MachineError> {
+        if addr < 0 || addr as usize >= self.mem.len() {
+            return Err(MachineError { kind: MachineErrorKind::MemoryOutOfBounds { addr }, pc: self.pc });
         }
+        Ok(addr as usize)
+    }
+
+    /// Reads a `len`-byte slice of memory starting at `addr`, bounds-checked
+    /// against the whole range rather than just its start.
+    pub fn read_bytes(&self, addr: Value, len: Value) -> Result<&[u8], MachineError> {
+        if len < 0 {
+            return Err(MachineError { kind: MachineErrorKind::MemoryOutOfBounds { addr }, pc: self.pc });
+        }
+        let start = self.check_addr(addr)?;
+        let end = start + len as usize;
+        if end > self.mem.len() {
+            return Err(MachineError { kind: MachineErrorKind::MemoryOutOfBounds { addr }, pc: self.pc });
+        }
+        Ok(&self.mem[start..end])
     }
 
     pub fn push(&mut self, value: Value) -> Result<(), MachineError> {
@@ -56,6 +132,17 @@ impl Machine {
         if self.fp >= CALLSTACK_SIZE - 1 {
             return Err(MachineError { kind: MachineErrorKind::CallStackOverflow, pc: self.pc })
         }
+        self.push_frame_raw(addr)
+    }
+
+    /// Pushes a frame into the one slot `push_frame` holds back, bypassing
+    /// its overflow check. Used to dispatch into every trap handler, so that
+    /// a `CallStackOverflow` trap, which fires only once `push_frame` has
+    /// found the call stack full, still has room for its return frame.
+    fn push_frame_raw(&mut self, addr: ProgramAddress) -> Result<(), MachineError> {
+        if self.fp >= CALLSTACK_SIZE {
+            return Err(MachineError { kind: MachineErrorKind::CallStackOverflow, pc: self.pc })
+        }
         self.call_stack[self.fp] = addr;
         self.fp += 1;
         Ok(())
@@ -69,7 +156,7 @@ impl Machine {
         Ok(self.call_stack[self.fp])
     }
 
-    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), MachineError> {
+    fn execute_instruction_raw(&mut self, instruction: Instruction) -> Result<(), MachineError> {
         match instruction {
             Instruction::Halt | Instruction::Noop => {},
             Instruction::LoadImmediate(n) => self.acc = n as i32,
@@ -88,7 +175,13 @@ impl Machine {
             Instruction::Add => self.acc += self.pop()?,
             Instruction::Sub => self.acc -= self.pop()?,
             Instruction::Mul => self.acc *= self.pop()?,
-            Instruction::Div => self.acc /= self.pop()?,
+            Instruction::Div => {
+                let divisor = self.pop()?;
+                if divisor == 0 {
+                    return Err(MachineError { kind: MachineErrorKind::DivisionByZero, pc: self.pc });
+                }
+                self.acc /= divisor;
+            }
             Instruction::Eq => self.acc = if self.acc == self.pop()? { 1 } else { 0 },
             Instruction::Neq =>  self.acc = if self.acc != self.pop()? { 1 } else { 0 },
             Instruction::Lt =>  self.acc = if self.acc < self.pop()? { 1 } else { 0 },
@@ -120,16 +213,159 @@ impl Machine {
             Instruction::Ret => {
                 self.pc = self.pop_frame()?;
             }
+            Instruction::Store8 => {
+                let addr = self.check_addr(self.acc)?;
+                let value = self.pop()?;
+                self.mem[addr] = value as u8;
+            }
+            Instruction::Load8 => {
+                let addr = self.check_addr(self.acc)?;
+                self.acc = self.mem[addr] as Value;
+            }
+            Instruction::RegisterTrap(kind, addr) => {
+                self.trap_handlers[kind.index()] = Some(addr);
+            }
+            // `step` intercepts `Syscall` before it ever reaches this match,
+            // dispatching to `Host` instead; see `step` below.
+            Instruction::Syscall(_) => unreachable!("Syscall is handled by step, not execute_instruction_raw"),
         }
         self.pc += 1;
         Ok(())
     }
 
-    pub fn run(&mut self, code: &[Instruction]) -> Result<(), MachineError> {
+    /// Runs one instruction, redirecting to a registered trap handler
+    /// instead of returning an error when the fault it raised has one.
+    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), MachineError> {
+        match self.execute_instruction_raw(instruction) {
+            Ok(()) => Ok(()),
+            Err(err) => match err.kind.trap_kind().and_then(|kind| self.trap_handlers[kind.index()]) {
+                Some(handler) => {
+                    self.push_frame_raw(err.pc)?;
+                    self.pc = handler;
+                    Ok(())
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Executes the single instruction at `pc`, dispatching
+    /// `Instruction::Syscall` to `host` rather than executing it in-place.
+    /// Returns [`StepOutcome::Halted`] without touching the machine state
+    /// if `pc` is past the end of `code` or on a `Halt`.
+    pub fn step(&mut self, code: &[Instruction], host: &mut dyn Host) -> Result<StepOutcome, MachineError> {
+        if (self.pc as usize) >= code.len() || code[self.pc as usize] == Instruction::Halt {
+            return Ok(StepOutcome::Halted);
+        }
+        let instruction = code[self.pc as usize];
+        if let Instruction::Syscall(num) = instruction {
+            host.syscall(num, self)?;
+            self.pc += 1;
+        } else {
+            self.execute_instruction(instruction)?;
+        }
+        Ok(StepOutcome::Stepped)
+    }
+
+    /// Runs `code` to completion by repeatedly stepping.
+    pub fn run(&mut self, code: &[Instruction], host: &mut dyn Host) -> Result<(), MachineError> {
         self.pc = 0;
-        while (self.pc as usize) < code.len() && code[self.pc as usize] != Instruction::Halt {
-            self.execute_instruction(code[self.pc as usize])?
+        loop {
+            match self.step(code, host)? {
+                StepOutcome::Halted => return Ok(()),
+                StepOutcome::Stepped => {}
+            }
         }
-        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StepOutcome {
+    Halted,
+    Stepped,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NoopHost;
+    impl Host for NoopHost {
+        fn syscall(&mut self, _num: u8, _m: &mut Machine) -> Result<(), MachineError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn call_stack_overflow_trap_handler_runs() {
+        let mut machine = Machine::new();
+        machine.trap_handlers[TrapKind::CallStackOverflow.index()] = Some(999);
+        for _ in 0..(CALLSTACK_SIZE - 1) {
+            machine.push_frame(0).expect("headroom remains");
+        }
+        machine
+            .execute_instruction(Instruction::Call(1))
+            .expect("CallStackOverflow trap handler should catch the overflow");
+        assert_eq!(machine.pc, 999);
+    }
+
+    #[test]
+    fn call_stack_overflow_without_handler_still_errors() {
+        let mut machine = Machine::new();
+        for _ in 0..(CALLSTACK_SIZE - 1) {
+            machine.push_frame(0).expect("headroom remains");
+        }
+        let err = machine.execute_instruction(Instruction::Call(1));
+        assert!(matches!(err, Err(MachineError { kind: MachineErrorKind::CallStackOverflow, .. })));
+    }
+
+    #[test]
+    fn load_data_copies_into_memory() {
+        let mut machine = Machine::new();
+        machine.load_data(&[1, 2, 3]);
+        assert_eq!(machine.read_bytes(0, 3).unwrap(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn store8_and_load8_round_trip_in_bounds() {
+        let mut machine = Machine::new();
+        machine.acc = 10;
+        machine.push(42).expect("stack has room");
+        machine.execute_instruction(Instruction::Store8).expect("address is in bounds");
+        machine.acc = 10;
+        machine.execute_instruction(Instruction::Load8).expect("address is in bounds");
+        assert_eq!(machine.acc, 42);
+    }
+
+    #[test]
+    fn store8_past_mem_size_is_a_memory_fault() {
+        let mut machine = Machine::new();
+        machine.acc = MEM_SIZE as Value;
+        machine.push(1).expect("stack has room");
+        let err = machine.execute_instruction(Instruction::Store8);
+        assert!(matches!(
+            err,
+            Err(MachineError { kind: MachineErrorKind::MemoryOutOfBounds { addr }, .. }) if addr == MEM_SIZE as Value
+        ));
+    }
+
+    #[test]
+    fn load8_below_zero_is_a_memory_fault() {
+        let mut machine = Machine::new();
+        machine.acc = -1;
+        let err = machine.execute_instruction(Instruction::Load8);
+        assert!(matches!(
+            err,
+            Err(MachineError { kind: MachineErrorKind::MemoryOutOfBounds { addr }, .. }) if addr == -1
+        ));
+    }
+
+    #[test]
+    fn step_with_noop_host() {
+        let code = [Instruction::LoadImmediate(5), Instruction::Halt];
+        let mut machine = Machine::new();
+        let mut host = NoopHost;
+        machine.run(&code, &mut host).expect("should halt cleanly");
+        assert_eq!(machine.acc, 5);
     }
 }
\ No newline at end of file
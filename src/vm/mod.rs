@@ -1,6 +1,12 @@
 pub mod archi;
 pub mod asm;
+pub mod bytecode;
+pub mod debugger;
+pub mod disasm;
+pub mod host;
 mod machine;
 
 pub type Machine = machine::Machine;
+pub type MachineError = machine::MachineError;
+pub type StepOutcome = machine::StepOutcome;
 pub type Instruction = archi::Instruction;